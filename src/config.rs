@@ -0,0 +1,136 @@
+// Copyright 2019 Federico Fissore
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env;
+use std::fs;
+
+/// How the client address is picked out of a `X-Forwarded-For` chain. See `ip_header` in
+/// `[server]`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IpHeaderMode {
+    Rightmost,
+    Leftmost,
+}
+
+impl Default for IpHeaderMode {
+    fn default() -> Self {
+        IpHeaderMode::Rightmost
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ServerConfig {
+    pub host: Option<String>,
+    pub port: Option<String>,
+    #[serde(default)]
+    pub ip_header: IpHeaderMode,
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct GeoipConfig {
+    pub location_database: Option<String>,
+    pub asn_database: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DnsConfig {
+    #[serde(default)]
+    pub allow_forward_lookup: bool,
+    #[serde(default)]
+    pub allow_reverse_lookup: bool,
+    #[serde(default)]
+    pub hide_private_range_ips: bool,
+    #[serde(default)]
+    pub hidden_suffixes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub geoip: GeoipConfig,
+    #[serde(default)]
+    pub dns: DnsConfig,
+}
+
+fn config_file_path() -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--config")
+        .and_then(|index| args.get(index + 1))
+        .cloned()
+        .or_else(|| env::var("GEOIP_RS_CONFIG").ok())
+}
+
+/// Loads the `[server]`/`[geoip]`/`[dns]` TOML config file pointed at by `--config` or
+/// `GEOIP_RS_CONFIG`, if any, then applies the legacy `GEOIP_RS_*` env vars on top as overrides,
+/// kept for backward compatibility.
+pub fn load() -> Config {
+    let mut config: Config = config_file_path()
+        .map(|path| {
+            let contents = fs::read_to_string(&path)
+                .unwrap_or_else(|_| panic!("unable to read config file {}", path));
+            toml::from_str(&contents)
+                .unwrap_or_else(|_| panic!("invalid config file {}", path))
+        })
+        .unwrap_or_default();
+
+    if let Ok(host) = env::var("GEOIP_RS_HOST") {
+        config.server.host = Some(host);
+    }
+    if let Ok(port) = env::var("GEOIP_RS_PORT") {
+        config.server.port = Some(port);
+    }
+    if let Ok(db_path) = env::var("GEOIP_RS_DB_PATH") {
+        config.geoip.location_database = Some(db_path);
+    }
+    if let Ok(asn_db_path) = env::var("GEOIP_RS_ASN_DB_PATH") {
+        config.geoip.asn_database = Some(asn_db_path);
+    }
+    if let Ok(value) = env::var("GEOIP_RS_ALLOW_FORWARD_LOOKUP") {
+        config.dns.allow_forward_lookup = value == "true";
+    }
+    if let Ok(value) = env::var("GEOIP_RS_ALLOW_REVERSE_LOOKUP") {
+        config.dns.allow_reverse_lookup = value == "true";
+    }
+    if let Ok(value) = env::var("GEOIP_RS_HIDE_PRIVATE_RANGE_IPS") {
+        config.dns.hide_private_range_ips = value == "true";
+    }
+    if let Ok(value) = env::var("GEOIP_RS_HIDDEN_SUFFIXES") {
+        config.dns.hidden_suffixes = value.split(',').map(|s| s.trim().to_string()).collect();
+    }
+
+    if config.geoip.location_database.is_none() {
+        let args: Vec<String> = env::args().collect();
+        let config_flag_value_index = args.iter().position(|arg| arg == "--config").map(|index| index + 1);
+
+        config.geoip.location_database = args
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(index, arg)| !arg.starts_with('-') && Some(*index) != config_flag_value_index)
+            .map(|(_, arg)| arg.clone());
+    }
+
+    if config.geoip.location_database.is_none() {
+        panic!("You must specify the db path, either in the config file, as a command line argument or as GEOIP_RS_DB_PATH env var");
+    }
+
+    config
+}
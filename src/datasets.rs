@@ -17,6 +17,7 @@ extern crate csv;
 use std::io;
 
 use csv::Reader;
+use ipnet::IpNet;
 
 use self::csv::Error;
 
@@ -31,7 +32,7 @@ struct RawBlock {
 
 #[derive(Debug, Clone)]
 pub struct Block {
-    pub network: String,
+    pub network: IpNet,
     pub geoname_id: u32,
     pub postal_code: String,
     pub latitude: f32,
@@ -45,7 +46,7 @@ pub fn parse_blocks_csv<R: io::Read>(source: R) -> impl Iterator<Item=Block> {
         .map(|result: Result<RawBlock, Error>| result.unwrap())
         .filter(|record| record.geoname_id.is_some() && record.latitude.is_some() && record.longitude.is_some())
         .map(|rawblock| Block {
-            network: rawblock.network,
+            network: rawblock.network.parse().unwrap(),
             geoname_id: rawblock.geoname_id.unwrap(),
             postal_code: rawblock.postal_code,
             latitude: rawblock.latitude.unwrap(),
@@ -81,6 +82,33 @@ pub fn parse_locations_csv<R: io::Read>(source: R) -> impl Iterator<Item=Locatio
         .map(|record: Result<Location, Error>| record.unwrap())
 }
 
+#[derive(Debug, Deserialize)]
+struct RawAsnBlock {
+    pub network: String,
+    pub autonomous_system_number: Option<u32>,
+    pub autonomous_system_organization: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsnBlock {
+    pub network: IpNet,
+    pub autonomous_system_number: u32,
+    pub autonomous_system_organization: String,
+}
+
+pub fn parse_asn_csv<R: io::Read>(source: R) -> impl Iterator<Item=AsnBlock> {
+    let reader = Reader::from_reader(source);
+
+    reader.into_deserialize()
+        .map(|result: Result<RawAsnBlock, Error>| result.unwrap())
+        .filter(|record| record.autonomous_system_number.is_some() && record.autonomous_system_organization.is_some())
+        .map(|rawblock| AsnBlock {
+            network: rawblock.network.parse().unwrap(),
+            autonomous_system_number: rawblock.autonomous_system_number.unwrap(),
+            autonomous_system_organization: rawblock.autonomous_system_organization.unwrap(),
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,7 +125,7 @@ network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,
         assert_eq!(2, blocks.len());
 
         let block = blocks.get(0).unwrap();
-        assert_eq!("1.0.0.0/24", block.network);
+        assert_eq!("1.0.0.0/24", block.network.to_string());
         assert_eq!(2077456, block.geoname_id);
         assert_eq!("", block.postal_code);
         assert_eq!(-33.4940, block.latitude);
@@ -129,4 +157,21 @@ geoname_id,locale_code,continent_code,continent_name,country_iso_code,country_na
         assert_eq!("Souni", location.city_name);
         assert_eq!("Asia/Nicosia", location.timezone);
     }
+
+    #[test]
+    fn can_read_asn_csv() {
+        let data = "
+network,autonomous_system_number,autonomous_system_organization
+1.0.0.0/24,13335,CLOUDFLARENET
+1.0.4.0/22,56203,Unknown
+1.0.16.0/24,,";
+
+        let blocks = parse_asn_csv(data.as_bytes()).collect::<Vec<AsnBlock>>();
+        assert_eq!(2, blocks.len());
+
+        let block = blocks.get(0).unwrap();
+        assert_eq!("1.0.0.0/24", block.network.to_string());
+        assert_eq!(13335, block.autonomous_system_number);
+        assert_eq!("CLOUDFLARENET", block.autonomous_system_organization);
+    }
 }
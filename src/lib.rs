@@ -22,77 +22,137 @@ extern crate serde_derive;
 
 use std::collections::HashMap;
 use std::io::Read;
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
-use ipnet::Ipv4Net;
+use ipnet::IpNet;
 
-use crate::datasets::{Block, Location};
+use crate::datasets::{AsnBlock, Block, Location};
 
 mod datasets;
 
-/// GeoIPDB is the struct holding both blocks (ip networks and their coordinates) and locations
-/// (contintent, country, etc corresponding to some coordinates)
+/// A single node of the binary trie (one level per address bit, no path compression) used to
+/// index values by network prefix.
+///
+/// A node at depth `n` represents every address sharing the first `n` bits of its path from the
+/// root. `value` is populated when a network was inserted with exactly that prefix length.
 #[derive(Debug)]
-pub struct GeoIPDB {
-    locations: HashMap<u32, Location>,
-    blocks: HashMap<u32, Vec<Block>>,
+struct TrieNode<T> {
+    children: [Option<Box<TrieNode<T>>>; 2],
+    value: Option<T>,
 }
 
-impl GeoIPDB {
-    /// Given a V4 ip network with a prefix lower than 16, it will expand it the corresponding networks with prefix 16
-    /// If instead the given network has a prefix greater or equal 16, no expansion occurs
-    fn expand_network(network: &Ipv4Net) -> Vec<u32> {
-        let prefix = network.prefix_len();
-
-        let expanded_networks;
-        if prefix < 16 {
-            expanded_networks = network
-                .subnets(16)
-                .unwrap()
-                .map(|network| GeoIPDB::ipnet_to_map_key(&network))
-                .collect();
-        } else {
-            expanded_networks = vec![GeoIPDB::ipnet_to_map_key(network)];
+impl<T> TrieNode<T> {
+    fn new() -> Self {
+        TrieNode {
+            children: [None, None],
+            value: None,
+        }
+    }
+
+    /// Inserts `value` at the node reached by following `bits` from the root, creating
+    /// intermediate nodes as needed.
+    fn insert(&mut self, bits: &[bool], value: T) {
+        let mut node = self;
+        for bit in bits {
+            node = node.children[*bit as usize].get_or_insert_with(|| Box::new(TrieNode::new()));
+        }
+        node.value = Some(value);
+    }
+
+    /// Walks `bits` from the root, returning the value held by the deepest visited node, which
+    /// is the longest (most specific) matching prefix.
+    fn longest_match(&self, bits: &[bool]) -> Option<&T> {
+        let mut node = self;
+        let mut best = node.value.as_ref();
+        for bit in bits {
+            match &node.children[*bit as usize] {
+                Some(child) => {
+                    node = child;
+                    if node.value.is_some() {
+                        best = node.value.as_ref();
+                    }
+                }
+                None => break,
+            }
         }
+        best
+    }
+}
+
+fn ipv4_bits(ip_address: Ipv4Addr) -> Vec<bool> {
+    let bits = u32::from(ip_address);
+    (0..32).map(|i| (bits >> (31 - i)) & 1 == 1).collect()
+}
+
+fn ipv6_bits(ip_address: Ipv6Addr) -> Vec<bool> {
+    let bits = u128::from(ip_address);
+    (0..128).map(|i| (bits >> (127 - i)) & 1 == 1).collect()
+}
 
-        expanded_networks
+fn network_bits(network: &IpNet) -> Vec<bool> {
+    let prefix_len = network.prefix_len() as usize;
+    match network.addr() {
+        IpAddr::V4(addr) => ipv4_bits(addr)[0..prefix_len].to_vec(),
+        IpAddr::V6(addr) => ipv6_bits(addr)[0..prefix_len].to_vec(),
     }
+}
 
-    /// Translates a V4 ip network into a u32 suitable to be used as key in the hashmap held by GeoIPDB
-    fn ipnet_to_map_key(ip_address: &Ipv4Net) -> u32 {
-        GeoIPDB::ipaddr_to_map_key(&ip_address.addr())
+/// Inserts `value`, keyed by `network`, into whichever of `v4`/`v6` matches its address family.
+fn insert_by_network<T>(v4: &mut TrieNode<T>, v6: &mut TrieNode<T>, network: IpNet, value: T) {
+    let bits = network_bits(&network);
+    match network {
+        IpNet::V4(_) => v4.insert(&bits, value),
+        IpNet::V6(_) => v6.insert(&bits, value),
     }
+}
 
-    /// Translates a V4 ip address into a u32 suitable to be used as key in the hashmap held by GeoIPDB
-    fn ipaddr_to_map_key(ip_address: &Ipv4Addr) -> u32 {
-        ip_address.octets()[0..2]
-            .iter()
-            .map(|n| u32::from(*n))
-            .scan(1_000, |state, value| {
-                let res = *state * value;
-                *state = *state / 1000;
-                Some(res)
-            })
-            .map(|n| u32::from(n))
-            .sum()
+/// Looks up the given ip address in whichever of `v4`/`v6` matches its address family.
+fn resolve_by_address<'a, T>(
+    v4: &'a TrieNode<T>,
+    v6: &'a TrieNode<T>,
+    ip_address: &str,
+) -> Option<&'a T> {
+    match ip_address.parse::<IpAddr>().unwrap() {
+        IpAddr::V4(ip_address) => v4.longest_match(&ipv4_bits(ip_address)),
+        IpAddr::V6(ip_address) => v6.longest_match(&ipv6_bits(ip_address)),
     }
+}
 
-    /// Creates a new GeoIPDB by parsing and loading the contents of a blocks CSV file and a location CSV file
-    pub fn new<R: Read + Sized>(blocks_csv_file: R, locations_csv_file: R) -> Self {
-        let mut blocks = HashMap::new();
+/// GeoIPDB is the struct holding blocks (ip networks and their coordinates), locations
+/// (contintent, country, etc corresponding to some coordinates) and, optionally, ASN blocks
+/// (ip networks and the autonomous system announcing them).
+///
+/// Blocks are indexed in binary radix tries, one per address family, so that `resolve` and
+/// `resolve_asn` can find the most specific (longest prefix) match for overlapping CIDR ranges
+/// in O(bits) time.
+///
+/// This is a CSV-backed lookup, separate from the geoip-rs binary, which serves requests from
+/// MaxMind's compiled `.mmdb` databases via the `maxminddb` crate instead. `GeoIPDB` (and
+/// `resolve_asn` in particular) is for consumers who want to build a lookup service directly
+/// from the raw GeoLite2 CSV snapshots rather than the binary format.
+#[derive(Debug)]
+pub struct GeoIPDB {
+    locations: HashMap<u32, Location>,
+    v4_blocks: TrieNode<Block>,
+    v6_blocks: TrieNode<Block>,
+    v4_asn_blocks: TrieNode<AsnBlock>,
+    v6_asn_blocks: TrieNode<AsnBlock>,
+}
 
-        datasets::parse_blocks_csv(blocks_csv_file)
-            .map(|block| {
-                let networks = GeoIPDB::expand_network(&block.network);
+impl GeoIPDB {
+    /// Creates a new GeoIPDB by parsing and loading the contents of a blocks CSV file and a
+    /// location CSV file, and, if provided, an ASN blocks CSV file.
+    pub fn new<R: Read + Sized>(
+        blocks_csv_file: R,
+        locations_csv_file: R,
+        asn_csv_file: Option<R>,
+    ) -> Self {
+        let mut v4_blocks = TrieNode::new();
+        let mut v6_blocks = TrieNode::new();
 
-                (block, networks)
-            })
-            .for_each(|(block, networks)| {
-                networks.iter().for_each(|network| {
-                    let blocks = blocks.entry(*network).or_insert(Vec::new());
-                    blocks.push(block.clone());
-                });
-            });
+        datasets::parse_blocks_csv(blocks_csv_file).for_each(|block| {
+            insert_by_network(&mut v4_blocks, &mut v6_blocks, block.network, block);
+        });
 
         let mut locations = HashMap::new();
 
@@ -100,19 +160,32 @@ impl GeoIPDB {
             locations.insert(location.geoname_id, location);
         });
 
-        GeoIPDB { locations, blocks }
+        let mut v4_asn_blocks = TrieNode::new();
+        let mut v6_asn_blocks = TrieNode::new();
+
+        if let Some(asn_csv_file) = asn_csv_file {
+            datasets::parse_asn_csv(asn_csv_file).for_each(|asn_block| {
+                insert_by_network(&mut v4_asn_blocks, &mut v6_asn_blocks, asn_block.network, asn_block);
+            });
+        }
+
+        GeoIPDB {
+            locations,
+            v4_blocks,
+            v6_blocks,
+            v4_asn_blocks,
+            v6_asn_blocks,
+        }
     }
 
     /// Looks for the given ip address in the db, returning the corresponding block, if any
     pub fn resolve(&self, ip_address: &str) -> Option<&Block> {
-        let ip_address = ip_address.parse::<Ipv4Addr>().unwrap();
-        let candidates = self.blocks.get(&GeoIPDB::ipaddr_to_map_key(&ip_address));
+        resolve_by_address(&self.v4_blocks, &self.v6_blocks, ip_address)
+    }
 
-        candidates.and_then(|candidates| {
-            candidates
-                .iter()
-                .find(|block| block.network.contains(&ip_address))
-        })
+    /// Looks for the given ip address in the ASN db, returning the corresponding block, if any
+    pub fn resolve_asn(&self, ip_address: &str) -> Option<&AsnBlock> {
+        resolve_by_address(&self.v4_asn_blocks, &self.v6_asn_blocks, ip_address)
     }
 
     /// Returns the location corresponding to the given id
@@ -125,35 +198,6 @@ impl GeoIPDB {
 mod tests {
     use super::*;
 
-    #[test]
-    #[ignore]
-    fn ipaddress_expansion() {
-        let ip1 = "172.16.0.0/26".parse::<Ipv4Net>().unwrap();
-        ip1.subnets(16).unwrap().for_each(|ip| println!("{:?}", ip));
-        let ip1 = "172.16.0.128/26".parse::<Ipv4Net>().unwrap();
-        ip1.subnets(16).unwrap().for_each(|ip| println!("{:?}", ip));
-    }
-
-    #[test]
-    fn ip_to_number() {
-        assert_eq!(
-            255255,
-            GeoIPDB::ipnet_to_map_key(&"255.255.255.0/24".parse::<Ipv4Net>().unwrap())
-        );
-        assert_eq!(
-            255255,
-            GeoIPDB::ipaddr_to_map_key(&"255.255.255.12".parse::<Ipv4Addr>().unwrap())
-        );
-        assert_eq!(
-            1000,
-            GeoIPDB::ipaddr_to_map_key(&"1.0.0.1".parse::<Ipv4Addr>().unwrap())
-        );
-        assert_eq!(
-            81030,
-            GeoIPDB::ipaddr_to_map_key(&"81.30.9.30".parse::<Ipv4Addr>().unwrap())
-        );
-    }
-
     #[test]
     fn can_resolve_ip() {
         let blocks = "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider,postal_code,latitude,longitude,accuracy_radius
@@ -165,7 +209,7 @@ mod tests {
 1809935,en,AS,Asia,CN,China,GD,Guangdong,,,,,Asia/Shanghai,0
 49518,en,AF,Africa,RW,Rwanda,,,,,,,Africa/Kigali,0";
 
-        let geoip_db = GeoIPDB::new(blocks.as_bytes(), locations.as_bytes());
+        let geoip_db = GeoIPDB::new(blocks.as_bytes(), locations.as_bytes(), None);
 
         let block = geoip_db.resolve("1.3.4.2").unwrap();
         assert_eq!("1.3.0.0/16", block.network.to_string());
@@ -188,9 +232,59 @@ mod tests {
         assert_eq!("Asia/Shanghai", location.timezone);
     }
 
+    #[test]
+    fn can_resolve_the_most_specific_overlapping_block() {
+        let blocks = "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider,postal_code,latitude,longitude,accuracy_radius
+1.3.0.0/16,1809935,1814991,,0,0,,23.1167,113.2500,50
+1.3.4.0/24,1811017,1814991,,0,0,,24.4798,118.0819,50";
+
+        let geoip_db = GeoIPDB::new(blocks.as_bytes(), "".as_bytes(), None);
+
+        let block = geoip_db.resolve("1.3.4.2").unwrap();
+        assert_eq!("1.3.4.0/24", block.network.to_string());
+
+        let block = geoip_db.resolve("1.3.5.2").unwrap();
+        assert_eq!("1.3.0.0/16", block.network.to_string());
+    }
+
+    #[test]
+    fn can_resolve_ipv6() {
+        let blocks = "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider,postal_code,latitude,longitude,accuracy_radius
+2001:db8::/32,1809935,1814991,,0,0,,23.1167,113.2500,50";
+
+        let geoip_db = GeoIPDB::new(blocks.as_bytes(), "".as_bytes(), None);
+
+        let block = geoip_db.resolve("2001:db8::1").unwrap();
+        assert_eq!("2001:db8::/32", block.network.to_string());
+        assert_eq!(true, geoip_db.resolve("2001:db9::1").is_none());
+    }
+
     #[test]
     fn cannot_resolve_ip() {
-        let geoip_db = GeoIPDB::new("".as_bytes(), "".as_bytes());
+        let geoip_db = GeoIPDB::new("".as_bytes(), "".as_bytes(), None);
         assert_eq!(true, geoip_db.resolve("1.2.3.4").is_none());
     }
+
+    #[test]
+    fn can_resolve_asn() {
+        let blocks = "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider,postal_code,latitude,longitude,accuracy_radius
+1.0.0.0/24,2077456,2077456,,0,0,,-33.4940,143.2104,1000";
+
+        let asn_blocks = "network,autonomous_system_number,autonomous_system_organization
+1.0.0.0/24,13335,CLOUDFLARENET";
+
+        let geoip_db = GeoIPDB::new(blocks.as_bytes(), "".as_bytes(), Some(asn_blocks.as_bytes()));
+
+        let asn_block = geoip_db.resolve_asn("1.0.0.1").unwrap();
+        assert_eq!(13335, asn_block.autonomous_system_number);
+        assert_eq!("CLOUDFLARENET", asn_block.autonomous_system_organization);
+
+        assert_eq!(true, geoip_db.resolve_asn("8.8.8.8").is_none());
+    }
+
+    #[test]
+    fn asn_is_none_when_no_asn_csv_is_given() {
+        let geoip_db = GeoIPDB::new("".as_bytes(), "".as_bytes(), None);
+        assert_eq!(true, geoip_db.resolve_asn("1.0.0.1").is_none());
+    }
 }
@@ -15,7 +15,7 @@
 #[macro_use]
 extern crate serde_derive;
 
-use std::env;
+use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 use std::sync::Arc;
@@ -27,11 +27,19 @@ use actix_web::App;
 use actix_web::HttpRequest;
 use actix_web::HttpResponse;
 use actix_web::HttpServer;
-use maxminddb::geoip2::City;
+use ipnet::IpNet;
+use maxminddb::geoip2::{Asn, City};
 use maxminddb::MaxMindDBError;
 use maxminddb::Reader;
 use memmap::Mmap;
 use serde_json;
+use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+use trust_dns_resolver::TokioAsyncResolver;
+
+use crate::config::DnsConfig;
+use crate::config::IpHeaderMode;
+
+mod config;
 
 #[derive(Serialize)]
 struct NonResolvedIPResponse<'a> {
@@ -54,6 +62,9 @@ struct ResolvedIPResponse<'a> {
     pub province_name: &'a str,
     pub city_name: &'a str,
     pub timezone: &'a str,
+    pub asn: u32,
+    pub asn_org: &'a str,
+    pub hostname: &'a str,
 }
 
 #[derive(Deserialize, Debug)]
@@ -63,29 +74,156 @@ struct QueryParams {
     callback: Option<String>,
 }
 
-fn ip_address_to_resolve(
+/// Whether `ip_address` falls within one of the configured trusted proxy CIDRs.
+fn is_trusted_proxy(ip_address: &IpAddr, trusted_proxies: &[IpNet]) -> bool {
+    trusted_proxies.iter().any(|network| network.contains(ip_address))
+}
+
+/// Parses a `X-Forwarded-For` header value into the chain of addresses it lists, left to right,
+/// silently dropping entries that are not valid addresses so a spoofed entry can't poison the
+/// rest of the chain.
+fn parse_forwarded_chain(header_value: &str) -> Vec<IpAddr> {
+    header_value
+        .split(',')
+        .filter_map(|candidate| candidate.trim().parse::<IpAddr>().ok())
+        .collect()
+}
+
+/// Picks the client address out of a `X-Forwarded-For` chain: in `Leftmost` mode, the address
+/// the first proxy claims to have received the request from; in `Rightmost` mode (the safer
+/// default), the rightmost entry that isn't one of our own trusted proxies, since anything to
+/// its left could have been injected by the client itself. Rightmost mode is only meaningful
+/// once at least one trusted proxy is configured, so with none configured (or none of the chain
+/// being untrusted) it returns `None` rather than trusting a client-controlled entry.
+fn client_ip_from_forwarded_chain(
+    chain: &[IpAddr],
+    ip_header_mode: IpHeaderMode,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    match ip_header_mode {
+        IpHeaderMode::Leftmost => chain.first().copied(),
+        IpHeaderMode::Rightmost => {
+            if trusted_proxies.is_empty() {
+                return None;
+            }
+
+            chain
+                .iter()
+                .rev()
+                .find(|ip_address| !is_trusted_proxy(ip_address, trusted_proxies))
+                .copied()
+        }
+    }
+}
+
+/// Determines which address to resolve, in order of precedence: an explicit `ip` query
+/// parameter (itself forward-resolved if it's a hostname), the `X-Forwarded-For` chain filtered
+/// through the configured trusted proxies, a single `X-Real-IP` header, and finally the
+/// connection peer. Every candidate is validated as a v4/v6 address before use, so a spoofed
+/// header can at worst be ignored, never crash the worker.
+async fn ip_address_to_resolve(
     query: &QueryParams,
     headers: &HeaderMap,
     remote_addr: Option<&str>,
-) -> String {
-    query
-        .ip
-        .as_ref()
-        .filter(|ip_address| {
-            ip_address.parse::<Ipv4Addr>().is_ok() || ip_address.parse::<Ipv6Addr>().is_ok()
-        })
-        .map(|s| s.to_owned())
-        .or_else(|| {
-            headers
-                .get("X-Real-IP")
-                .map(|s| s.to_str().unwrap().to_string())
-        })
-        .or_else(|| {
-            remote_addr
-                .map(|ip_port| ip_port.split(':').take(1).last().unwrap())
-                .map(|ip| ip.to_string())
+    dns: Option<&Dns>,
+    ip_header_mode: IpHeaderMode,
+    trusted_proxies: &[IpNet],
+) -> Option<String> {
+    if let Some(ip_address) = &query.ip {
+        if ip_address.parse::<Ipv4Addr>().is_ok() || ip_address.parse::<Ipv6Addr>().is_ok() {
+            return Some(ip_address.to_owned());
+        }
+
+        if let Some(dns) = dns {
+            if let Some(resolved) = dns.forward_lookup(ip_address).await {
+                return Some(resolved.to_string());
+            }
+        }
+    }
+
+    if let Some(forwarded_for) = headers.get("X-Forwarded-For").and_then(|value| value.to_str().ok()) {
+        let chain = parse_forwarded_chain(forwarded_for);
+        if let Some(client_ip) = client_ip_from_forwarded_chain(&chain, ip_header_mode, trusted_proxies) {
+            return Some(client_ip.to_string());
+        }
+    }
+
+    if let Some(real_ip) = headers
+        .get("X-Real-IP")
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| value.parse::<IpAddr>().is_ok())
+    {
+        return Some(real_ip.to_string());
+    }
+
+    remote_addr
+        .map(|ip_port| ip_port.split(':').take(1).last().unwrap())
+        .filter(|ip_address| ip_address.parse::<IpAddr>().is_ok())
+        .map(|ip_address| ip_address.to_string())
+}
+
+/// Whether `ip_address` falls in a range reserved for private use (RFC1918/ULA), loopback or
+/// IPv4 link-local (169.254.0.0/16), the ranges that `hide_private_range_ips` suppresses PTR
+/// results for.
+fn is_private_range(ip_address: &IpAddr) -> bool {
+    match ip_address {
+        IpAddr::V4(ip_address) => {
+            ip_address.is_private() || ip_address.is_loopback() || ip_address.is_link_local()
+        }
+        // fc00::/7 is the IPv6 Unique Local Address range; there is no stable is_unique_local yet.
+        IpAddr::V6(ip_address) => {
+            ip_address.is_loopback() || (ip_address.segments()[0] & 0xfe00) == 0xfc00
+        }
+    }
+}
+
+/// Shared DNS subsystem: forward-resolves hostnames passed as the `ip` query parameter, and
+/// reverse-resolves the hostname of a query address for the `hostname` response field.
+#[derive(Clone)]
+struct Dns {
+    resolver: TokioAsyncResolver,
+    allow_forward_lookup: bool,
+    allow_reverse_lookup: bool,
+    hide_private_range_ips: bool,
+    hidden_suffixes: Vec<String>,
+}
+
+impl Dns {
+    async fn forward_lookup(&self, hostname: &str) -> Option<IpAddr> {
+        if !self.allow_forward_lookup {
+            return None;
+        }
+
+        self.resolver
+            .lookup_ip(hostname)
+            .await
+            .ok()
+            .and_then(|lookup| lookup.iter().next())
+    }
+
+    async fn reverse_lookup(&self, ip_address: IpAddr) -> Option<String> {
+        if !self.allow_reverse_lookup {
+            return None;
+        }
+
+        if self.hide_private_range_ips && is_private_range(&ip_address) {
+            return None;
+        }
+
+        let hostname = self
+            .resolver
+            .reverse_lookup(ip_address)
+            .await
+            .ok()
+            .and_then(|lookup| lookup.iter().next().map(|name| name.to_string()));
+
+        hostname.filter(|hostname| {
+            !self
+                .hidden_suffixes
+                .iter()
+                .any(|suffix| hostname.ends_with(suffix.as_str()))
         })
-        .expect("unable to find ip address to resolve")
+    }
 }
 
 fn get_language(query: &QueryParams) -> String {
@@ -98,154 +236,425 @@ fn get_language(query: &QueryParams) -> String {
 
 struct Db {
     db: Arc<Reader<Mmap>>,
+    asn_db: Option<Arc<Reader<Mmap>>>,
+    dns: Option<Dns>,
+    ip_header_mode: IpHeaderMode,
+    trusted_proxies: Vec<IpNet>,
 }
 
-async fn index(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
-    //let query = Query::<QueryParams>::extract(&req).await.unwrap();
+/// Every field `index` and the single-field plaintext routes can serve, resolved once per
+/// request so there is a single source of truth for both the JSON document and the plaintext
+/// endpoints.
+struct ResolvedFields {
+    ip_address: String,
+    latitude: f64,
+    longitude: f64,
+    postal_code: String,
+    continent_code: String,
+    continent_name: String,
+    country_code: String,
+    country_name: String,
+    region_code: String,
+    region_name: String,
+    province_code: String,
+    province_name: String,
+    city_name: String,
+    timezone: String,
+    asn: u32,
+    asn_org: String,
+    hostname: String,
+}
+
+impl<'a> From<&'a ResolvedFields> for ResolvedIPResponse<'a> {
+    fn from(fields: &'a ResolvedFields) -> Self {
+        ResolvedIPResponse {
+            ip_address: &fields.ip_address,
+            latitude: &fields.latitude,
+            longitude: &fields.longitude,
+            postal_code: &fields.postal_code,
+            continent_code: &fields.continent_code,
+            continent_name: &fields.continent_name,
+            country_code: &fields.country_code,
+            country_name: &fields.country_name,
+            region_code: &fields.region_code,
+            region_name: &fields.region_name,
+            province_code: &fields.province_code,
+            province_name: &fields.province_name,
+            city_name: &fields.city_name,
+            timezone: &fields.timezone,
+            asn: fields.asn,
+            asn_org: &fields.asn_org,
+            hostname: &fields.hostname,
+        }
+    }
+}
 
-    let language = get_language(&query);
-    let ip_address = ip_address_to_resolve(&query, req.headers(), req.connection_info().remote());
+enum LookupResult {
+    Resolved(ResolvedFields),
+    NotResolved(String),
+}
 
-    let lookup: Result<City, MaxMindDBError> = data.db.lookup(ip_address.parse().unwrap());
+/// Which of the expensive optional fields a route actually serves, so `resolve_all` only pays
+/// for the ASN lookup and the reverse-DNS round-trip when the caller needs them.
+#[derive(Clone, Copy)]
+struct FieldsNeeded {
+    asn: bool,
+    hostname: bool,
+}
 
-    let geoip = match lookup {
-        Ok(geoip) => {
-            let region = geoip
-                .subdivisions
-                .as_ref()
-                .filter(|subdivs| !subdivs.is_empty())
-                .and_then(|subdivs| subdivs.get(0));
-
-            let province = geoip
-                .subdivisions
-                .as_ref()
-                .filter(|subdivs| subdivs.len() > 1)
-                .and_then(|subdivs| subdivs.get(1));
-
-            let res = ResolvedIPResponse {
-                ip_address: &ip_address,
-                latitude: geoip
-                    .location
-                    .as_ref()
-                    .and_then(|loc| loc.latitude.as_ref())
-                    .unwrap_or(&0.0),
-                longitude: geoip
-                    .location
-                    .as_ref()
-                    .and_then(|loc| loc.longitude.as_ref())
-                    .unwrap_or(&0.0),
-                postal_code: geoip
-                    .postal
-                    .as_ref()
-                    .and_then(|postal| postal.code.as_ref())
-                    .map(String::as_str)
-                    .unwrap_or(""),
-                continent_code: geoip
-                    .continent
-                    .as_ref()
-                    .and_then(|cont| cont.code.as_ref())
-                    .map(String::as_str)
-                    .unwrap_or(""),
-                continent_name: geoip
-                    .continent
-                    .as_ref()
-                    .and_then(|cont| cont.names.as_ref())
-                    .and_then(|names| names.get(&language))
-                    .map(String::as_str)
-                    .unwrap_or(""),
-                country_code: geoip
-                    .country
-                    .as_ref()
-                    .and_then(|country| country.iso_code.as_ref())
-                    .map(String::as_str)
-                    .unwrap_or(""),
-                country_name: geoip
-                    .country
-                    .as_ref()
-                    .and_then(|country| country.names.as_ref())
-                    .and_then(|names| names.get(&language))
-                    .map(String::as_str)
-                    .unwrap_or(""),
-                region_code: region
-                    .and_then(|subdiv| subdiv.iso_code.as_ref())
-                    .map(String::as_ref)
-                    .unwrap_or(""),
-                region_name: region
-                    .and_then(|subdiv| subdiv.names.as_ref())
-                    .and_then(|names| names.get(&language))
-                    .map(String::as_ref)
-                    .unwrap_or(""),
-                province_code: province
-                    .and_then(|subdiv| subdiv.iso_code.as_ref())
-                    .map(String::as_ref)
-                    .unwrap_or(""),
-                province_name: province
-                    .and_then(|subdiv| subdiv.names.as_ref())
-                    .and_then(|names| names.get(&language))
-                    .map(String::as_ref)
-                    .unwrap_or(""),
-                city_name: geoip
-                    .city
-                    .as_ref()
-                    .and_then(|city| city.names.as_ref())
-                    .and_then(|names| names.get(&language))
-                    .map(String::as_str)
-                    .unwrap_or(""),
-                timezone: geoip
-                    .location
-                    .as_ref()
-                    .and_then(|loc| loc.time_zone.as_ref())
-                    .map(String::as_str)
-                    .unwrap_or(""),
-            };
-            serde_json::to_string(&res).ok()
+impl FieldsNeeded {
+    const NONE: FieldsNeeded = FieldsNeeded { asn: false, hostname: false };
+    const ALL: FieldsNeeded = FieldsNeeded { asn: true, hostname: true };
+    const ASN: FieldsNeeded = FieldsNeeded { asn: true, hostname: false };
+    const HOSTNAME: FieldsNeeded = FieldsNeeded { asn: false, hostname: true };
+}
+
+/// Resolves the query address once, producing either the full set of geo/ASN/DNS fields or,
+/// when the address is not in the geo database, just the address that was looked up. `needs`
+/// skips the ASN lookup and/or the reverse-DNS round-trip for routes that don't serve them.
+/// Fails with a plaintext 400 response when no candidate address could be validated.
+async fn resolve_all(
+    req: &HttpRequest,
+    data: &Db,
+    query: &QueryParams,
+    needs: FieldsNeeded,
+) -> Result<LookupResult, HttpResponse> {
+    let language = get_language(query);
+    let ip_address = ip_address_to_resolve(
+        query,
+        req.headers(),
+        req.connection_info().remote(),
+        data.dns.as_ref(),
+        data.ip_header_mode,
+        &data.trusted_proxies,
+    )
+    .await
+    .ok_or_else(|| {
+        HttpResponse::BadRequest()
+            .content_type("text/plain; charset=utf-8")
+            .body("unable to determine a valid ip address to resolve\n")
+    })?;
+    let parsed_ip_address: IpAddr = ip_address.parse().unwrap();
+
+    let lookup: Result<City, MaxMindDBError> = data.db.lookup(parsed_ip_address);
+
+    let asn_lookup: Option<Asn> = if needs.asn {
+        data.asn_db
+            .as_ref()
+            .and_then(|asn_db| asn_db.lookup(parsed_ip_address).ok())
+    } else {
+        None
+    };
+    let asn = asn_lookup
+        .as_ref()
+        .and_then(|asn| asn.autonomous_system_number)
+        .unwrap_or(0);
+    let asn_org = asn_lookup
+        .as_ref()
+        .and_then(|asn| asn.autonomous_system_organization.clone())
+        .unwrap_or_default();
+
+    let hostname = if needs.hostname {
+        match &data.dns {
+            Some(dns) => dns.reverse_lookup(parsed_ip_address).await.unwrap_or_default(),
+            None => String::new(),
         }
-        Err(_) => serde_json::to_string(&NonResolvedIPResponse {
-            ip_address: &ip_address,
-        })
-        .ok(),
-    }
-    .unwrap();
+    } else {
+        String::new()
+    };
+
+    let geoip = match lookup {
+        Ok(geoip) => geoip,
+        Err(_) => return Ok(LookupResult::NotResolved(ip_address)),
+    };
+
+    let region = geoip
+        .subdivisions
+        .as_ref()
+        .filter(|subdivs| !subdivs.is_empty())
+        .and_then(|subdivs| subdivs.get(0));
+
+    let province = geoip
+        .subdivisions
+        .as_ref()
+        .filter(|subdivs| subdivs.len() > 1)
+        .and_then(|subdivs| subdivs.get(1));
+
+    Ok(LookupResult::Resolved(ResolvedFields {
+        ip_address,
+        latitude: geoip
+            .location
+            .as_ref()
+            .and_then(|loc| loc.latitude)
+            .unwrap_or(0.0),
+        longitude: geoip
+            .location
+            .as_ref()
+            .and_then(|loc| loc.longitude)
+            .unwrap_or(0.0),
+        postal_code: geoip
+            .postal
+            .as_ref()
+            .and_then(|postal| postal.code.clone())
+            .unwrap_or_default(),
+        continent_code: geoip
+            .continent
+            .as_ref()
+            .and_then(|cont| cont.code.clone())
+            .unwrap_or_default(),
+        continent_name: geoip
+            .continent
+            .as_ref()
+            .and_then(|cont| cont.names.as_ref())
+            .and_then(|names| names.get(&language))
+            .cloned()
+            .unwrap_or_default(),
+        country_code: geoip
+            .country
+            .as_ref()
+            .and_then(|country| country.iso_code.clone())
+            .unwrap_or_default(),
+        country_name: geoip
+            .country
+            .as_ref()
+            .and_then(|country| country.names.as_ref())
+            .and_then(|names| names.get(&language))
+            .cloned()
+            .unwrap_or_default(),
+        region_code: region
+            .and_then(|subdiv| subdiv.iso_code.clone())
+            .unwrap_or_default(),
+        region_name: region
+            .and_then(|subdiv| subdiv.names.as_ref())
+            .and_then(|names| names.get(&language))
+            .cloned()
+            .unwrap_or_default(),
+        province_code: province
+            .and_then(|subdiv| subdiv.iso_code.clone())
+            .unwrap_or_default(),
+        province_name: province
+            .and_then(|subdiv| subdiv.names.as_ref())
+            .and_then(|names| names.get(&language))
+            .cloned()
+            .unwrap_or_default(),
+        city_name: geoip
+            .city
+            .as_ref()
+            .and_then(|city| city.names.as_ref())
+            .and_then(|names| names.get(&language))
+            .cloned()
+            .unwrap_or_default(),
+        timezone: geoip
+            .location
+            .as_ref()
+            .and_then(|loc| loc.time_zone.clone())
+            .unwrap_or_default(),
+        asn,
+        asn_org,
+        hostname,
+    }))
+}
 
+fn respond_json(query: &QueryParams, body: String) -> HttpResponse {
     match &query.callback {
         Some(callback) => HttpResponse::Ok()
             .content_type("application/javascript; charset=utf-8")
-            .body(format!(";{}({});", callback, geoip)),
+            .body(format!(";{}({});", callback, body)),
         None => HttpResponse::Ok()
             .content_type("application/json; charset=utf-8")
-            .body(geoip),
+            .body(body),
     }
 }
 
-fn db_file_path() -> String {
-    let db_file_env_var = env::var("GEOIP_RS_DB_PATH");
-    if db_file_env_var.is_ok() {
-        return db_file_env_var.unwrap();
+fn respond_plaintext(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(body)
+}
+
+fn accepts_plaintext(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(actix_web::http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.contains("text/plain"))
+        .unwrap_or(false)
+}
+
+fn plaintext_dump(fields: &ResolvedFields) -> String {
+    format!(
+        "ip_address: {}\ncontinent_code: {}\ncontinent_name: {}\ncountry_code: {}\ncountry_name: {}\nregion_code: {}\nregion_name: {}\nprovince_code: {}\nprovince_name: {}\ncity_name: {}\npostal_code: {}\nlatitude: {}\nlongitude: {}\ntimezone: {}\nasn: {}\nasn_org: {}\nhostname: {}\n",
+        fields.ip_address,
+        fields.continent_code,
+        fields.continent_name,
+        fields.country_code,
+        fields.country_name,
+        fields.region_code,
+        fields.region_name,
+        fields.province_code,
+        fields.province_name,
+        fields.city_name,
+        fields.postal_code,
+        fields.latitude,
+        fields.longitude,
+        fields.timezone,
+        fields.asn,
+        fields.asn_org,
+        fields.hostname,
+    )
+}
+
+async fn index(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    //let query = Query::<QueryParams>::extract(&req).await.unwrap();
+
+    let result = match resolve_all(&req, &data, &query, FieldsNeeded::ALL).await {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+
+    if accepts_plaintext(&req) {
+        return respond_plaintext(match &result {
+            LookupResult::Resolved(fields) => plaintext_dump(fields),
+            LookupResult::NotResolved(ip_address) => format!("ip_address: {}\n", ip_address),
+        });
     }
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 {
-        return args[1].to_string();
+    let body = match &result {
+        LookupResult::Resolved(fields) => {
+            serde_json::to_string(&ResolvedIPResponse::from(fields)).unwrap()
+        }
+        LookupResult::NotResolved(ip_address) => serde_json::to_string(&NonResolvedIPResponse {
+            ip_address,
+        })
+        .unwrap(),
+    };
+
+    respond_json(&query, body)
+}
+
+async fn ip(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    match resolve_all(&req, &data, &query, FieldsNeeded::NONE).await {
+        Ok(LookupResult::Resolved(fields)) => respond_plaintext(fields.ip_address),
+        Ok(LookupResult::NotResolved(ip_address)) => respond_plaintext(ip_address),
+        Err(response) => response,
+    }
+}
+
+async fn country(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    match resolve_all(&req, &data, &query, FieldsNeeded::NONE).await {
+        Ok(LookupResult::Resolved(fields)) => respond_plaintext(fields.country_name),
+        Ok(LookupResult::NotResolved(_)) => respond_plaintext(String::new()),
+        Err(response) => response,
     }
+}
 
-    panic!("You must specify the db path, either as a command line argument or as GEOIP_RS_DB_PATH env var");
+async fn country_code(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    match resolve_all(&req, &data, &query, FieldsNeeded::NONE).await {
+        Ok(LookupResult::Resolved(fields)) => respond_plaintext(fields.country_code),
+        Ok(LookupResult::NotResolved(_)) => respond_plaintext(String::new()),
+        Err(response) => response,
+    }
 }
+
+async fn city(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    match resolve_all(&req, &data, &query, FieldsNeeded::NONE).await {
+        Ok(LookupResult::Resolved(fields)) => respond_plaintext(fields.city_name),
+        Ok(LookupResult::NotResolved(_)) => respond_plaintext(String::new()),
+        Err(response) => response,
+    }
+}
+
+async fn coordinates(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    match resolve_all(&req, &data, &query, FieldsNeeded::NONE).await {
+        Ok(LookupResult::Resolved(fields)) => {
+            respond_plaintext(format!("{},{}", fields.latitude, fields.longitude))
+        }
+        Ok(LookupResult::NotResolved(_)) => respond_plaintext(String::new()),
+        Err(response) => response,
+    }
+}
+
+async fn asn(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    match resolve_all(&req, &data, &query, FieldsNeeded::ASN).await {
+        Ok(LookupResult::Resolved(fields)) if fields.asn != 0 => {
+            respond_plaintext(format!("AS{} {}", fields.asn, fields.asn_org))
+        }
+        Ok(_) => respond_plaintext(String::new()),
+        Err(response) => response,
+    }
+}
+
+async fn hostname(req: HttpRequest, data: web::Data<Db>, web::Query(query): web::Query<QueryParams>) -> HttpResponse {
+    match resolve_all(&req, &data, &query, FieldsNeeded::HOSTNAME).await {
+        Ok(LookupResult::Resolved(fields)) => respond_plaintext(fields.hostname),
+        Ok(LookupResult::NotResolved(_)) => respond_plaintext(String::new()),
+        Err(response) => response,
+    }
+}
+
+async fn build_dns(dns_config: &DnsConfig) -> Option<Dns> {
+    if !dns_config.allow_forward_lookup && !dns_config.allow_reverse_lookup {
+        return None;
+    }
+
+    let resolver = TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+        .await
+        .expect("unable to build the DNS resolver");
+
+    Some(Dns {
+        resolver,
+        allow_forward_lookup: dns_config.allow_forward_lookup,
+        allow_reverse_lookup: dns_config.allow_reverse_lookup,
+        hide_private_range_ips: dns_config.hide_private_range_ips,
+        hidden_suffixes: dns_config.hidden_suffixes.clone(),
+    })
+}
+
 #[actix_rt::main]
 async fn main() {
     dotenv::from_path(".env").ok();
 
-    let host = env::var("GEOIP_RS_HOST").unwrap_or_else(|_| String::from("127.0.0.1"));
-    let port = env::var("GEOIP_RS_PORT").unwrap_or_else(|_| String::from("3000"));
+    let config = config::load();
+
+    let host = config.server.host.unwrap_or_else(|| String::from("127.0.0.1"));
+    let port = config.server.port.unwrap_or_else(|| String::from("3000"));
 
     println!("Listening on http://{}:{}", host, port);
 
-    let db = Arc::new(Reader::open_mmap(db_file_path()).unwrap());
+    let db = Arc::new(Reader::open_mmap(config.geoip.location_database.unwrap()).unwrap());
+    let asn_db = config
+        .geoip
+        .asn_database
+        .map(|path| Arc::new(Reader::open_mmap(path).unwrap()));
+    let dns = build_dns(&config.dns).await;
+    let ip_header_mode = config.server.ip_header;
+    let trusted_proxies: Vec<IpNet> = config
+        .server
+        .trusted_proxies
+        .iter()
+        .map(|cidr| {
+            cidr.parse()
+                .unwrap_or_else(|_| panic!("invalid trusted proxy CIDR: {}", cidr))
+        })
+        .collect();
 
     HttpServer::new(move || {
         App::new()
-            .data(Db { db: db.clone() })
+            .data(Db {
+                db: db.clone(),
+                asn_db: asn_db.clone(),
+                dns: dns.clone(),
+                ip_header_mode,
+                trusted_proxies: trusted_proxies.clone(),
+            })
             .wrap(Cors::new().send_wildcard().finish())
             .route("/", web::route().to(index))
+            .route("/ip", web::route().to(ip))
+            .route("/country", web::route().to(country))
+            .route("/country-code", web::route().to(country_code))
+            .route("/city", web::route().to(city))
+            .route("/coordinates", web::route().to(coordinates))
+            .route("/asn", web::route().to(asn))
+            .route("/hostname", web::route().to(hostname))
     })
     .bind(format!("{}:{}", host, port))
     .unwrap_or_else(|_| panic!("Can not bind to {}:{}", host, port))